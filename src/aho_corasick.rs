@@ -0,0 +1,115 @@
+use std::collections::{HashMap, VecDeque};
+
+struct Node {
+    children: HashMap<u8, usize>,
+    fail: usize,
+    // Indices into `patterns` accepted at this node, pulled in from the
+    // failure link so a single lookup sees every pattern ending here.
+    outputs: Vec<usize>,
+}
+
+impl Node {
+    fn empty() -> Node {
+        Node { children: HashMap::new(), fail: 0, outputs: vec![] }
+    }
+}
+
+/// Multi-pattern substring matcher built once over a fixed set of patterns.
+///
+/// A trie of the patterns is extended with Aho-Corasick failure links (each
+/// node's failure pointer is the longest proper suffix of its path that is
+/// itself a trie node, computed via a BFS over the trie), so a single pass
+/// over a haystack finds every pattern occurring in it in
+/// O(haystack length + matches) instead of O(patterns * haystack length).
+pub struct AhoCorasick {
+    nodes: Vec<Node>,
+    patterns: Vec<String>,
+}
+
+impl AhoCorasick {
+    pub fn new(patterns: Vec<String>) -> AhoCorasick {
+        let mut nodes = vec![Node::empty()];
+        for (idx, pattern) in patterns.iter().enumerate() {
+            let mut cur = 0;
+            for &byte in pattern.as_bytes() {
+                cur = match nodes[cur].children.get(&byte) {
+                    Some(&next) => next,
+                    None => {
+                        nodes.push(Node::empty());
+                        let next = nodes.len() - 1;
+                        nodes[cur].children.insert(byte, next);
+                        next
+                    }
+                };
+            }
+            nodes[cur].outputs.push(idx);
+        }
+
+        let mut queue = VecDeque::new();
+        let root_children: Vec<usize> = nodes[0].children.values().copied().collect();
+        for child in root_children {
+            nodes[child].fail = 0;
+            queue.push_back(child);
+        }
+        while let Some(v) = queue.pop_front() {
+            let children: Vec<(u8, usize)> = nodes[v].children.iter().map(|(&b, &c)| (b, c)).collect();
+            for (byte, child) in children {
+                let mut f = nodes[v].fail;
+                while f != 0 && !nodes[f].children.contains_key(&byte) {
+                    f = nodes[f].fail;
+                }
+                let fail = nodes[f].children.get(&byte).copied().unwrap_or(0);
+                nodes[child].fail = fail;
+                let inherited = nodes[fail].outputs.clone();
+                nodes[child].outputs.extend(inherited);
+                queue.push_back(child);
+            }
+        }
+
+        AhoCorasick { nodes, patterns }
+    }
+
+    /// Scan `haystack` and return the index of the longest pattern occurring
+    /// in it, preferring the deepest accepting node so that patterns sharing
+    /// a prefix don't get confused with one another.
+    pub fn find_longest_match(&self, haystack: &str) -> Option<usize> {
+        let mut state = 0;
+        let mut best: Option<usize> = None;
+        for &byte in haystack.as_bytes() {
+            while state != 0 && !self.nodes[state].children.contains_key(&byte) {
+                state = self.nodes[state].fail;
+            }
+            state = self.nodes[state].children.get(&byte).copied().unwrap_or(0);
+            for &pattern_idx in &self.nodes[state].outputs {
+                let is_longer = match best {
+                    None => true,
+                    Some(b) => self.patterns[pattern_idx].len() > self.patterns[b].len(),
+                };
+                if is_longer {
+                    best = Some(pattern_idx);
+                }
+            }
+        }
+        best
+    }
+
+    pub fn pattern(&self, idx: usize) -> &str {
+        &self.patterns[idx]
+    }
+
+    /// Scan `haystack` and return the index of every pattern occurring in
+    /// it, in the order their matches are found (a pattern matching more
+    /// than once is reported once per occurrence).
+    pub fn find_all_matches(&self, haystack: &str) -> Vec<usize> {
+        let mut state = 0;
+        let mut found = vec![];
+        for &byte in haystack.as_bytes() {
+            while state != 0 && !self.nodes[state].children.contains_key(&byte) {
+                state = self.nodes[state].fail;
+            }
+            state = self.nodes[state].children.get(&byte).copied().unwrap_or(0);
+            found.extend(self.nodes[state].outputs.iter().copied());
+        }
+        found
+    }
+}
@@ -1,13 +1,17 @@
 mod util;
 mod ref_graph;
+mod aho_corasick;
+mod title_matcher;
+mod notes;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::fs::File;
 use std::io;
 use std::path::PathBuf;
 use clap::Parser;
-use git2::{Commit, Oid, Repository, Revwalk, Sort};
+use git2::{Commit, DiffOptions, Error, Oid, Repository, Revwalk, Sort};
 use crate::ref_graph::RefGraph;
+use crate::title_matcher::TitleMatcher;
 use crate::util::{parse_commit_description, read_lines_from_bufreader};
 
 #[derive(Parser)]
@@ -17,13 +21,17 @@ struct Args {
     #[clap(short, long)]
     repo: PathBuf,
 
-    /// Commit-ish of the first commit to be inspected
-    #[clap(long)]
-    first_commit: String,
+    /// Revisions to inspect, `git rev-list`/`git log` style: bare tips
+    /// (e.g. a tag or branch), `A..B` ranges, `A...B` symmetric-difference
+    /// ranges, and `^C` exclusions to hide a subtree. At least one must be
+    /// given.
+    #[clap(required = true)]
+    revisions: Vec<String>,
 
-    /// Commit-ish of the last commit to be inspected
-    #[clap(long)]
-    last_commit: Option<String>,
+    /// Only consider commits touching one of these paths, like
+    /// `git log -- <path>...`. May be given multiple times.
+    #[clap(long = "path")]
+    paths: Vec<String>,
 
     /// Print summary results for the listed commits only
     #[clap(short, long, group = "check-mode")]
@@ -36,6 +44,22 @@ struct Args {
     /// Follow "Fixes:" tags and reverts
     #[clap(long)]
     no_notices: bool,
+
+    /// Skip patch-id equivalence detection (finding un-annotated
+    /// backports/cherry-picks); useful when diffing every commit in a
+    /// large range is too expensive
+    #[clap(long)]
+    no_patch_id: bool,
+
+    /// Persist results as git notes under `--notes-ref`, in addition to
+    /// printing them
+    #[clap(long)]
+    write_notes: bool,
+
+    /// Ref under which results are recorded as git notes when `--write-notes`
+    /// is set
+    #[clap(long, default_value = "refs/notes/fixed-searcher")]
+    notes_ref: String,
 }
 
 fn read_commits<'a>(args: &Args, repo: &'a Repository, commit_list: &'a [Commit<'a>]) -> Vec<Commit<'a>> {
@@ -44,16 +68,14 @@ fn read_commits<'a>(args: &Args, repo: &'a Repository, commit_list: &'a [Commit<
     } else {
         read_lines_from_bufreader(io::stdin())
     };
-    
-    let title_mapping = commit_list
-        .iter()
-        .enumerate()
-        .map(|(idx, commit)| (commit.summary().unwrap_or("<no title>"), idx))
-        .collect::<HashMap<_, _>>();
-    
+
+    // Built once so every line is resolved in O(line length) instead of
+    // scanning the whole commit range per line.
+    let matcher = TitleMatcher::new(commit_list, &lines);
+
     lines
         .iter()
-        .flat_map(|line| parse_commit_description(line, repo, commit_list, &title_mapping))
+        .flat_map(|line| parse_commit_description(line, repo, commit_list, &matcher))
         .collect()
 }
 
@@ -61,14 +83,11 @@ fn configure_walk<'a>(repo: &'a Repository, args: &Args) -> Revwalk<'a> {
     let mut walk = repo.revwalk()
         .unwrap_or_else(|e| panic!("Failed to get revwalk: {}", e));
 
-    if let Some(last) = &args.last_commit {
-        walk.push_range(&format!("{}..{}", args.first_commit, last))
-            .unwrap_or_else(|e| panic!(
-                "Failed to set range {}..{}: {}", args.first_commit, last, e
-            ));
-    } else {
-        walk.push_ref(&args.first_commit)
-            .unwrap_or_else(|e| panic!("Failed to push ref {}: {}", args.first_commit, e));
+    for spec in &args.revisions {
+        if let Err(e) = apply_revision_spec(repo, &mut walk, spec) {
+            eprintln!("Invalid revision argument '{spec}': {e}");
+            std::process::exit(1);
+        }
     }
 
     walk.set_sorting(Sort::REVERSE)
@@ -76,6 +95,49 @@ fn configure_walk<'a>(repo: &'a Repository, args: &Args) -> Revwalk<'a> {
     walk
 }
 
+/// Parses and applies one `git rev-list`-style revision argument: a bare tip
+/// or a `^C` exclusion resolves to a single object and is pushed/hidden
+/// directly, and an `A..B` range is handed to `Revwalk::push_range`, which
+/// understands it the same way `git log` does. `A...B` is handled by hand
+/// instead, since libgit2's revwalk doesn't implement symmetric differences
+/// at all: it resolves to everything reachable from `A` or `B` but not from
+/// their merge base, so both endpoints are pushed and the merge base hidden.
+fn apply_revision_spec(repo: &Repository, walk: &mut Revwalk, spec: &str) -> Result<(), Error> {
+    if let Some(excluded) = spec.strip_prefix('^') {
+        let oid = repo.revparse_single(excluded)?.id();
+        walk.hide(oid)
+    } else if let Some((a, b)) = spec.split_once("...") {
+        let a = repo.revparse_single(a)?.id();
+        let b = repo.revparse_single(b)?.id();
+        let base = repo.merge_base(a, b)?;
+        walk.push(a)?;
+        walk.push(b)?;
+        walk.hide(base)
+    } else if spec.contains("..") {
+        walk.push_range(spec)
+    } else {
+        let oid = repo.revparse_single(spec)?.id();
+        walk.push(oid)
+    }
+}
+
+/// Whether a commit's diff against its first parent touches any of `paths`.
+/// An empty `paths` list means no filtering is requested.
+fn touches_paths(repo: &Repository, oid: Oid, paths: &[String]) -> bool {
+    if paths.is_empty() {
+        return true;
+    }
+    let commit = repo.find_commit(oid).unwrap();
+    let old_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+    let new_tree = commit.tree().ok();
+    let mut diff_opts = DiffOptions::new();
+    for path in paths {
+        diff_opts.pathspec(path);
+    }
+    repo.diff_tree_to_tree(old_tree.as_ref(), new_tree.as_ref(), Some(&mut diff_opts))
+        .is_ok_and(|diff| diff.deltas().len() > 0)
+}
+
 fn main() {
     colog::init();
     let args = Args::parse();
@@ -83,7 +145,10 @@ fn main() {
         .unwrap_or_else(|e| panic!("Failed to open repository: {}", e));
 
     let walker = configure_walk(&repo, &args);
-    let ref_graph = RefGraph::new(&repo, walker.into_iter());
+    let walked_oids = walker
+        .into_iter()
+        .filter(|res| res.as_ref().is_ok_and(|&oid| touches_paths(&repo, oid, &args.paths)));
+    let ref_graph = RefGraph::new(&repo, walked_oids, !args.no_patch_id);
     
     if args.check_commits {
         let inspected_commits = ref_graph.get_commits(&repo);
@@ -107,19 +172,27 @@ fn main() {
                 );
                 for reference in &fixed {
                     println!(
-                        "    {} (\"{}\")", 
-                        reference, 
-                        repo.find_commit(*reference)
-                            .unwrap()
-                            .summary()
-                            .unwrap_or("<no summary>"),
+                        "    {} (\"{}\") [{}]",
+                        reference,
+                        ref_graph.summary_of(&repo, *reference),
+                        ref_graph.tag_for(&repo, *reference).unwrap_or_else(|| "(unreleased)".to_string()),
                     )
                 }
             }
-            found_new_commits.extend(fixed.into_iter());
+            if args.write_notes {
+                let body = fixed.iter().map(Oid::to_string).collect::<Vec<_>>().join("\n");
+                if let Some(diff) = notes::diff_note(&repo, &args.notes_ref, commit.id(), &body) {
+                    println!("    (changed since previous run: {diff})");
+                }
+                notes::write_missing_note(&repo, &args.notes_ref, commit.id(), &fixed);
+            }
+            found_new_commits.extend(fixed);
         }
         println!("Summary: found {} probably missing commits", found_new_commits.len());
     } else {
         ref_graph.dump_info(&repo, args.no_notices);
+        if args.write_notes {
+            notes::write_reference_notes(&repo, &ref_graph, &args.notes_ref);
+        }
     }
 }
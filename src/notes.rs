@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+use git2::{Oid, Repository, Signature};
+use log::{debug, info, warn};
+use crate::ref_graph::RefGraph;
+
+/// Persists this tool's findings into the repository as git notes under
+/// `notes_ref`, rather than only printing them: every inspected commit that
+/// at least one other commit directly references gets a note listing those
+/// commits and how (`RefType`); commits with no direct references get no
+/// note. This way a prior run's results stay queryable with ordinary
+/// `git log --notes=<notes_ref>` instead of being ephemeral stdout output.
+pub fn write_reference_notes(repo: &Repository, graph: &RefGraph, notes_ref: &str) {
+    let signature = note_signature(repo);
+    for &oid in graph.get_oids() {
+        let direct = graph.get_direct_references(oid);
+        if direct.is_empty() {
+            continue;
+        }
+        let body = direct
+            .iter()
+            .map(|(referrer, ref_type)| format!("{referrer} {:?}", ref_type))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let Some(diff) = diff_note(repo, notes_ref, oid, &body) {
+            println!("Commit {oid}: references changed since previous run: {diff}");
+        }
+        write_note_if_changed(repo, &signature, notes_ref, oid, &body);
+    }
+}
+
+/// Attaches a note to a checked commit naming the upstream commits that
+/// `check_commits` flagged as "probably missing" for it.
+pub fn write_missing_note(repo: &Repository, notes_ref: &str, oid: Oid, missing: &[Oid]) {
+    let signature = note_signature(repo);
+    let body = missing.iter().map(Oid::to_string).collect::<Vec<_>>().join("\n");
+    write_note_if_changed(repo, &signature, notes_ref, oid, &body);
+}
+
+/// Reads back a previously-written note, if any, so a later run can diff its
+/// fresh findings against it and skip rewriting a note that is already up to
+/// date. The findings themselves are still recomputed on every run.
+pub fn read_note(repo: &Repository, notes_ref: &str, oid: Oid) -> Option<String> {
+    repo.find_note(Some(notes_ref), oid)
+        .ok()
+        .and_then(|note| note.message().map(str::to_string))
+}
+
+/// Compares `new_body` (the note this run would write for `oid`, in the
+/// same line-per-entry format `write_reference_notes`/`write_missing_note`
+/// use) against the note a previous run left under `notes_ref`. Returns
+/// `None` when there is nothing to report (no prior note, or nothing
+/// changed), so callers can skip printing when a run is a no-op repeat of
+/// the last one. Entries are compared as sets since note lines have no
+/// meaningful order.
+pub fn diff_note(repo: &Repository, notes_ref: &str, oid: Oid, new_body: &str) -> Option<String> {
+    let previous = read_note(repo, notes_ref, oid)?;
+    if previous == new_body {
+        return None;
+    }
+    let prev_lines: HashSet<&str> = previous.lines().collect();
+    let new_lines: HashSet<&str> = new_body.lines().collect();
+    let mut parts = vec![];
+    let added: Vec<&str> = new_lines.difference(&prev_lines).copied().collect();
+    if !added.is_empty() {
+        parts.push(format!("+ {}", added.join(", ")));
+    }
+    let removed: Vec<&str> = prev_lines.difference(&new_lines).copied().collect();
+    if !removed.is_empty() {
+        parts.push(format!("- {}", removed.join(", ")));
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("; "))
+}
+
+fn write_note_if_changed(repo: &Repository, signature: &Signature, notes_ref: &str, oid: Oid, body: &str) {
+    if read_note(repo, notes_ref, oid).as_deref() == Some(body) {
+        debug!("Note for commit {oid} under {notes_ref} is already up to date, skipping");
+        return;
+    }
+    if let Err(e) = repo.note(signature, signature, Some(notes_ref), oid, body, true) {
+        warn!("Failed to write note for commit {oid} under {notes_ref}: {e}");
+    } else {
+        info!("Wrote note for commit {oid} under {notes_ref}");
+    }
+}
+
+fn note_signature(repo: &Repository) -> Signature<'static> {
+    repo.signature()
+        .unwrap_or_else(|_| Signature::now("git-fixed-searcher", "git-fixed-searcher@localhost").unwrap())
+}
@@ -1,6 +1,6 @@
-use std::cell::RefCell;
+use std::cell::{OnceCell, RefCell};
 use std::cmp::max;
-use std::collections::HashMap;
+use std::collections::{BinaryHeap, HashMap};
 use git2::{Commit, Error, Oid, Repository};
 use log::{debug, info, warn};
 use crate::util::{extract_references, get_commit_by_ref_entry, RefType};
@@ -12,23 +12,59 @@ pub struct RefGraph {
     // DFS internal info
     flag: RefCell<u64>,
     visited: RefCell<Vec<u64>>,
+    // Oid -> name of the earliest tag whose history contains it, computed
+    // lazily the first time a caller asks for it.
+    tag_index: RefCell<Option<HashMap<Oid, String>>>,
+    // Oid -> summary of every walked commit, and a sorted index of their hex
+    // oids for binary-searching short-hash lookups. Both are built lazily,
+    // the first time `summary_of`/`find_commit_by_hash` is actually called,
+    // so a run that never needs them never pays for the object reads.
+    summary_cache: OnceCell<HashMap<Oid, String>>,
+    prefix_index: OnceCell<Vec<(String, Oid)>>,
 }
 
 impl RefGraph {
     // Commits should go in a commit order - from oldest to newest one
-    pub fn new(repo: &Repository, commits: impl Iterator<Item=Result<Oid, Error>>) -> RefGraph {
+    //
+    // `detect_equivalent` additionally buckets commits by patch-id to find
+    // un-annotated backports/cherry-picks (see `add_equivalence_edges`);
+    // disable it for large ranges where diffing every commit is too costly.
+    pub fn new(
+        repo: &Repository,
+        commits: impl Iterator<Item=Result<Oid, Error>>,
+        detect_equivalent: bool,
+    ) -> RefGraph {
+        let commits: Vec<Oid> = commits.flatten().collect();
         let mut graph = RefGraph {
             referenced_by: vec![],
             hash_to_id: HashMap::new(),
             id_to_hash: vec![],
             flag: RefCell::new(0),
             visited: RefCell::new(vec![]),
+            tag_index: RefCell::new(None),
+            summary_cache: OnceCell::new(),
+            prefix_index: OnceCell::new(),
         };
-        for oid in commits.flatten() {
-            let id = graph.lookup_or_alloc(&oid);
+
+        // Register every walked commit up front so that below, a reference
+        // to any of them - regardless of where it falls in the walk order -
+        // can already be resolved through `lookup`. Without this, a commit
+        // referencing one that is walk-order-later (e.g. across unrelated
+        // branches merged out of title/date order) would have its reference
+        // dropped with the "commit order is wrong" log below, even though
+        // both ends are in range. Note this does widen what can match: a
+        // reference whose hash resolves to an in-range commit is now linked
+        // wherever that commit falls in the walk (e.g. on another branch),
+        // since order is no longer a filter.
+        for &oid in &commits {
+            graph.lookup_or_alloc(&oid);
+        }
+
+        for &oid in &commits {
+            let id = graph.lookup(&oid).unwrap();
             let mut added_edges: Vec<(usize, RefType)> = vec![];
             for referenced in extract_references(&repo.find_commit(oid).unwrap()) {
-                let Some(ref_commit) = get_commit_by_ref_entry(repo, &referenced) else {
+                let Some(ref_commit) = get_commit_by_ref_entry(repo, &graph, &referenced) else {
                     warn!(
                         "Commit {} references a commit that cannot be found!\n\
                         Hash: {}\n\
@@ -57,10 +93,168 @@ impl RefGraph {
                 debug!("Adding ref: {ref_id} -> {id}, type {:?}", t);
             }
         }
-        
+
+        if detect_equivalent {
+            graph.add_equivalence_edges(repo, &commits);
+        }
+
         graph
     }
 
+    /// Bucket commits by the patch-id of their diff against their first
+    /// parent (normalized by libgit2 so line numbers and context don't
+    /// matter, which is exactly what makes a backport/cherry-pick hash the
+    /// same as its original). Buckets with more than one commit get mutual
+    /// `Equivalent` edges, so a cherry-pick that never cites the commit it
+    /// backports still surfaces as a reference of it. Merge commits have no
+    /// single meaningful patch-id and are skipped.
+    fn add_equivalence_edges(&mut self, repo: &Repository, commits: &[Oid]) {
+        let mut buckets: HashMap<Oid, Vec<usize>> = HashMap::new();
+        for &oid in commits {
+            let commit = repo.find_commit(oid).unwrap();
+            if commit.parent_count() > 1 {
+                continue;
+            }
+            let old_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+            let new_tree = commit.tree().ok();
+            let patch_id = repo
+                .diff_tree_to_tree(old_tree.as_ref(), new_tree.as_ref(), None)
+                .and_then(|diff| diff.patchid(None));
+            match patch_id {
+                Ok(patch_id) => buckets.entry(patch_id).or_default().push(self.lookup(&oid).unwrap()),
+                Err(e) => warn!("Failed to compute patch-id for commit {oid}: {e}"),
+            }
+        }
+
+        for ids in buckets.values().filter(|ids| ids.len() > 1) {
+            for &a in ids {
+                for &b in ids {
+                    if a != b {
+                        self.add_reference(a, b, RefType::Equivalent);
+                        debug!("Adding equivalence ref: {a} -> {b}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Records that `referrer` references `referenced` with the given
+    /// `RefType`, merging into an edge already present for the same pair
+    /// (keeping the strongest type) instead of adding a duplicate - the
+    /// textual-reference pass and the patch-id equivalence pass can both
+    /// independently discover the same (referrer, referenced) pair.
+    fn add_reference(&mut self, referenced: usize, referrer: usize, t: RefType) {
+        match self.referenced_by[referenced].iter_mut().find(|(r, _)| *r == referrer) {
+            Some(existing) => existing.1 = max(existing.1, t),
+            None => self.referenced_by[referenced].push((referrer, t)),
+        }
+    }
+
+    /// Name of the earliest release tag whose history contains `oid`, i.e.
+    /// a `git describe --contains`-style annotation, or `None` if no tag
+    /// reaches it yet. Computed once across the whole repository and cached
+    /// on the graph so repeated lookups are cheap.
+    pub fn tag_for(&self, repo: &Repository, oid: Oid) -> Option<String> {
+        if self.tag_index.borrow().is_none() {
+            *self.tag_index.borrow_mut() = Some(Self::build_tag_index(repo));
+        }
+        self.tag_index.borrow().as_ref().unwrap().get(&oid).cloned()
+    }
+
+    /// Peels every tag to its target commit, then runs the classic
+    /// describe-contains propagation: push all tagged commits into a
+    /// priority queue ordered by commit time (newest first), and walk
+    /// ancestors, at each step relaxing a parent's current best (oldest)
+    /// tag with the one being propagated. Because commits are (re-)queued
+    /// whenever a better tag is found for them, the final value left for a
+    /// commit once the queue drains is the earliest tag that contains it.
+    fn build_tag_index(repo: &Repository) -> HashMap<Oid, String> {
+        fn relax(
+            best: &mut HashMap<Oid, (i64, String)>,
+            heap: &mut BinaryHeap<(i64, Oid)>,
+            oid: Oid,
+            time: i64,
+            name: &str,
+        ) {
+            let improves = match best.get(&oid) {
+                Some((known, _)) => time < *known,
+                None => true,
+            };
+            if improves {
+                best.insert(oid, (time, name.to_string()));
+                heap.push((time, oid));
+            }
+        }
+
+        let mut best: HashMap<Oid, (i64, String)> = HashMap::new();
+        let mut heap: BinaryHeap<(i64, Oid)> = BinaryHeap::new();
+
+        for tag_name in repo.tag_names(None).unwrap().iter().flatten() {
+            let Some(target) = repo
+                .revparse_single(&format!("refs/tags/{tag_name}"))
+                .ok()
+                .and_then(|obj| obj.peel_to_commit().ok())
+            else {
+                continue;
+            };
+            relax(&mut best, &mut heap, target.id(), target.time().seconds(), tag_name);
+        }
+
+        while let Some((time, oid)) = heap.pop() {
+            // A better (earlier) tag was found for this commit after it was
+            // queued; that entry already made it back onto the heap.
+            if best.get(&oid).map(|(known, _)| *known) != Some(time) {
+                continue;
+            }
+            let name = best[&oid].1.clone();
+            let Ok(commit) = repo.find_commit(oid) else { continue };
+            for parent in commit.parents() {
+                relax(&mut best, &mut heap, parent.id(), time, &name);
+            }
+        }
+
+        best.into_iter().map(|(oid, (_, name))| (oid, name)).collect()
+    }
+
+    /// Summary of `oid`, read from a cache of every walked commit's summary
+    /// built on first use, instead of re-parsing the commit object on every
+    /// call.
+    pub fn summary_of(&self, repo: &Repository, oid: Oid) -> String {
+        let cache = self.summary_cache.get_or_init(|| {
+            self.id_to_hash
+                .iter()
+                .map(|&oid| {
+                    let summary = repo.find_commit(oid).unwrap().summary().unwrap_or("<no summary>").to_string();
+                    (oid, summary)
+                })
+                .collect()
+        });
+        cache.get(&oid).cloned().unwrap_or_else(|| "<no summary>".to_string())
+    }
+
+    /// Resolves a (possibly short) hash against the walked commit range
+    /// first, binary-searching a cached, sorted index of their hex oids,
+    /// and only falls back to a full `find_commit_by_prefix` scan of the
+    /// whole object database when no in-range commit matches the prefix (or
+    /// more than one does - an ambiguous prefix is handed to
+    /// `find_commit_by_prefix` to report the same way it would for the whole
+    /// database, rather than silently picking one candidate).
+    pub fn find_commit_by_hash<'a>(&self, repo: &'a Repository, hash: &str) -> Option<Commit<'a>> {
+        let index = self.prefix_index.get_or_init(|| {
+            let mut index: Vec<(String, Oid)> = self.id_to_hash.iter().map(|&oid| (oid.to_string(), oid)).collect();
+            index.sort();
+            index
+        });
+        let start = index.partition_point(|(hex, _)| hex.as_str() < hash);
+        let mut matching = index[start..].iter().take_while(|(hex, _)| hex.starts_with(hash));
+        if let Some((_, oid)) = matching.next() {
+            if matching.next().is_none() {
+                return repo.find_commit(*oid).ok();
+            }
+        }
+        repo.find_commit_by_prefix(hash).ok()
+    }
+
     fn lookup(&self, oid: &Oid) -> Option<usize> {
         self.hash_to_id.get(oid).cloned()
     }
@@ -105,6 +299,18 @@ impl RefGraph {
             .collect()
     }
 
+    /// Commits that directly reference `oid`, together with how (`RefType`),
+    /// without following the reference chain transitively.
+    pub fn get_direct_references(&self, oid: Oid) -> Vec<(Oid, RefType)> {
+        let Some(v) = self.lookup(&oid) else {
+            return vec![];
+        };
+        self.referenced_by[v]
+            .iter()
+            .map(|&(u, t)| (self.id_to_hash[u], t))
+            .collect()
+    }
+
     pub fn get_references(&self, oid: Oid, no_notices: bool) -> Vec<Oid> {
         let Some(v) = self.lookup(&oid) else {
             info!("Commit with hash {} not found, someone may still blame it", oid);
@@ -118,14 +324,15 @@ impl RefGraph {
             let oid = &self.id_to_hash[i];
             let referenced_by = self.get_references_by_id(i, no_notices);
             if referenced_by.is_empty() {
-                println!("Commit {oid} (\"{}\") is not mentioned anywhere", 
-                    repo.find_commit(*oid).unwrap().summary().unwrap_or("<no summary>"));
+                println!("Commit {oid} (\"{}\") is not mentioned anywhere",
+                    self.summary_of(repo, *oid));
             } else {
-                println!("Found references of commit {oid} (\"{}\")", 
-                    repo.find_commit(*oid).unwrap().summary().unwrap_or("<no summary>"));
+                println!("Found references of commit {oid} (\"{}\")",
+                    self.summary_of(repo, *oid));
                 for ref_oid in referenced_by {
-                    println!("  {ref_oid} (\"{}\")", 
-                        repo.find_commit(ref_oid).unwrap().summary().unwrap_or("<no summary>"));
+                    println!("  {ref_oid} (\"{}\") [{}]",
+                        self.summary_of(repo, ref_oid),
+                        self.tag_for(repo, ref_oid).unwrap_or_else(|| "(unreleased)".to_string()));
                 }
             }
         }
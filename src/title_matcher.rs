@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use git2::Commit;
+use crate::aho_corasick::AhoCorasick;
+
+/// Index built once per `read_commits` call so resolving an input line to a
+/// commit in `commit_list` no longer costs a full scan per line.
+///
+/// Three lookups are tried, cheapest first: an exact title match, then the
+/// longest commit title occurring inside the line (via a forward
+/// Aho-Corasick automaton over the distinct commit summaries), then the
+/// rarer case where the line is itself only a fragment of a title, answered
+/// by a second, smaller automaton built over the distinct input lines and
+/// run once against every summary (collecting *every* line that occurs
+/// inside that summary, not just the longest) while the index is
+/// constructed.
+pub struct TitleMatcher {
+    exact: HashMap<String, usize>,
+    forward: AhoCorasick,
+    forward_commit_of: Vec<usize>,
+    reverse_hit: HashMap<String, usize>,
+}
+
+impl TitleMatcher {
+    pub fn new(commit_list: &[Commit], lines: &[String]) -> TitleMatcher {
+        let mut exact = HashMap::new();
+        let mut patterns = vec![];
+        let mut forward_commit_of = vec![];
+        let mut seen_titles = HashMap::new();
+        for (idx, commit) in commit_list.iter().enumerate() {
+            // Skip commits with no summary so the literal placeholder
+            // "<no title>" can never accidentally match a real line.
+            let Some(title) = commit.summary() else { continue };
+            // Mirrors the baseline's `HashMap::from_iter` over `(title, idx)`
+            // pairs, which keeps the *last* (newest) commit on a collision.
+            exact.insert(title.to_string(), idx);
+            seen_titles.entry(title.to_string()).or_insert_with(|| {
+                patterns.push(title.to_string());
+                forward_commit_of.push(idx);
+            });
+        }
+        let forward = AhoCorasick::new(patterns);
+
+        let mut distinct_lines = HashMap::new();
+        for line in lines {
+            distinct_lines.entry(line.clone()).or_insert(());
+        }
+        let reverse = AhoCorasick::new(distinct_lines.into_keys().collect());
+
+        // Record every input line that occurs as a substring of the title,
+        // not just the longest one, so a title embedding two unrelated
+        // lines (e.g. "Fix null pointer in parser and update docs") still
+        // resolves both of them.
+        let mut reverse_hit = HashMap::new();
+        for (idx, commit) in commit_list.iter().enumerate() {
+            let Some(title) = commit.summary() else { continue };
+            for pattern_idx in reverse.find_all_matches(title) {
+                reverse_hit.entry(reverse.pattern(pattern_idx).to_string()).or_insert(idx);
+            }
+        }
+
+        TitleMatcher { exact, forward, forward_commit_of, reverse_hit }
+    }
+
+    /// Resolve `line` to the index (into the `commit_list` the matcher was
+    /// built from) of the commit whose title it describes, if any.
+    pub fn resolve(&self, line: &str) -> Option<usize> {
+        if let Some(&idx) = self.exact.get(line) {
+            return Some(idx);
+        }
+        if let Some(pattern_idx) = self.forward.find_longest_match(line) {
+            return Some(self.forward_commit_of[pattern_idx]);
+        }
+        self.reverse_hit.get(line).copied()
+    }
+}
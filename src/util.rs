@@ -1,9 +1,10 @@
-use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Read};
 use git2::{Commit, Repository};
 use lazy_static::lazy_static;
 use log::warn;
 use regex::Regex;
+use crate::ref_graph::RefGraph;
+use crate::title_matcher::TitleMatcher;
 
 lazy_static! {
     // NOTE: commit messages contain all symbols except '"'.
@@ -25,6 +26,23 @@ pub enum RefType {
     Note,
     Fix,
     Revert,
+    // Two commits whose diffs share a patch-id: an un-annotated backport or
+    // cherry-pick. Treated as the strongest signal since it is derived from
+    // the actual change rather than from a possibly-missing commit message.
+    Equivalent,
+}
+
+impl RefType {
+    /// Whether an edge of this type should be followed while walking
+    /// references. `Note`s are only informational and are skipped when
+    /// `no_notices` is set; every other kind of edge is a real relationship
+    /// between commits and is always followed.
+    pub fn should_follow(&self, no_notices: bool) -> bool {
+        match self {
+            RefType::Note => !no_notices,
+            RefType::Fix | RefType::Revert | RefType::Equivalent => true,
+        }
+    }
 }
 
 pub struct RefEntry {
@@ -62,8 +80,8 @@ pub fn extract_references(commit: &Commit) -> Vec<RefEntry> {
         .collect()
 }
 
-pub fn get_commit_by_ref_entry<'a>(repo: &'a Repository, ref_entry: &RefEntry) -> Option<Commit<'a>> {
-    let found = repo.find_commit_by_prefix(&ref_entry.hash).ok();
+pub fn get_commit_by_ref_entry<'a>(repo: &'a Repository, graph: &RefGraph, ref_entry: &RefEntry) -> Option<Commit<'a>> {
+    let found = graph.find_commit_by_hash(repo, &ref_entry.hash);
     found.inspect(|commit| {
         if commit.summary().is_none() || ref_entry.title != commit.summary().unwrap() {
             warn!("\
@@ -114,10 +132,10 @@ fn check_commit_titles(real_title: &str, got_title: &str, verbose: bool) -> bool
 }
 
 pub fn parse_commit_description<'a>(
-    line: &str, 
-    repo: &'a Repository, 
+    line: &str,
+    repo: &'a Repository,
     commit_list: &[Commit<'a>],
-    title_mapping: &HashMap<&'a str, usize>,
+    matcher: &TitleMatcher,
 ) -> Option<Commit<'a>> {
     if let Some(cap) = config_hash_and_msg.captures(line) {
         let hash = cap.get(1).unwrap().as_str();
@@ -139,21 +157,9 @@ pub fn parse_commit_description<'a>(
             None
         }
     } else {
-        // It is a commit description, check if there is exactly the same title
-        // If no - iterate over all and check(Aho-Corasick algorithm would be nice here)
-        if let Some(idx) = title_mapping.get(line) {
-            return Some(commit_list[*idx].clone());
-        }
-        commit_list
-            .iter()
-            .find_map(|commit|
-                if commit.summary().is_some_and(|s| 
-                    check_commit_titles(s, line, false) || check_commit_titles(line, s, false)
-                ) {
-                    Some(commit.clone())
-                } else {
-                    None
-                }
-            )
+        // It is a commit description: resolve it through the Aho-Corasick
+        // title index built once in `read_commits`, instead of scanning
+        // every commit for each line.
+        matcher.resolve(line).map(|idx| commit_list[idx].clone())
     }
 }